@@ -0,0 +1,460 @@
+//! The `fesh_comp` command line: argument parsing and one handler per subcommand, all on top of
+//! the library.
+
+use super::*;
+use std::fs;
+use std::time::Instant;
+
+const VALUE_FLAGS: &[&str] = &[
+    "--exclude-section", "--restore", "--manifest", "--search", "--level", "--parallel-code", "--threads",
+    "--input-offset", "--input-length", "--small-threshold", "--integrity",
+    "--level-code", "--level-text", "--level-str", "--level-other", "--level-num", "--level-eh", "--level-debug",
+    "--zstd", "--zstd-code", "--zstd-text", "--zstd-str", "--zstd-other", "--zstd-num", "--zstd-eh", "--zstd-debug",
+];
+
+pub(crate) struct Cli {
+    positional: Vec<String>,
+    opts: Vec<(String, Option<String>)>,
+}
+
+impl Cli {
+    pub(crate) fn parse(args: &[String]) -> Result<Cli, String> {
+        let mut positional = Vec::new();
+        let mut opts = Vec::new();
+        let mut it = args.iter();
+        while let Some(a) = it.next() {
+            if a.starts_with("--") {
+                if VALUE_FLAGS.contains(&a.as_str()) {
+                    let v = it.next().ok_or_else(|| format!("{} requires a value", a))?;
+                    opts.push((a.clone(), Some(v.clone())));
+                } else {
+                    opts.push((a.clone(), None));
+                }
+            } else {
+                positional.push(a.clone());
+            }
+        }
+        Ok(Cli { positional, opts })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.opts.iter().any(|(k, _)| k == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).pop()
+    }
+
+    fn values(&self, name: &str) -> Vec<&str> {
+        self.opts.iter().filter(|(k, _)| k == name).filter_map(|(_, v)| v.as_deref()).collect()
+    }
+}
+
+const USAGE: &str = "usage: fesh_comp <compress|decompress|compare|verify-format|container-overhead> <input> [output] [options]\n       fesh_comp compress <input> <output> --exclude-section <name>... [--drop]  (saves them to <output>.excl)\n       fesh_comp decompress <input> <output> --restore <output>.excl\n       fesh_comp verify <input> [options]\n       fesh_comp verify-against <blob> <original>\n       fesh_comp info <input.fes>\n       fesh_comp explain <input>\n       fesh_comp analyze <input>\n       fesh_comp split <input> <dir>\n       fesh_comp join <dir> <output>\n       fesh_comp blob-diff <old.fes> <new.fes> <patch>\n       fesh_comp blob-patch <old.fes> <patch> <new.fes>\n       fesh_comp archive <out.fesa> <inputs...> [--dedupe-streams] [--integrity crc32|crc64|sha256]\n       fesh_comp extract <archive.fesa> <out-dir>\n       fesh_comp bench-corpus <dir> [--csv] [--threads N] [options]";
+
+#[derive(Debug)]
+pub(crate) enum CliError {
+    Mismatch(String),
+    Usage(String),
+    Io(String),
+    Decode(String),
+}
+
+impl CliError {
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Mismatch(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Io(_) => 3,
+            CliError::Decode(_) => 4,
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            CliError::Mismatch(m) | CliError::Usage(m) | CliError::Io(m) | CliError::Decode(m) => m,
+        }
+    }
+}
+
+/// `compare --compare-xz`: plain `xz -9e` on the untransformed input (same preset, xz's default
+/// pb=2) and how far `fesh_len` is below it.
+pub(crate) fn compare_xz(data: &[u8], fesh_len: usize) -> [String; 2] {
+    let xz = compress_xz_opts(data, &lzma_options(9 | PRESET_EXTREME, 2, choose_dict_size(data.len()), None));
+    let saved = 100.0 - fesh_len as f64 * 100.0 / xz.len().max(1) as f64;
+    [
+        format!("xz -9e:      {} bytes ({:.2}%)", xz.len(), xz.len() as f64 * 100.0 / data.len().max(1) as f64),
+        format!("vs xz:       {:+} bytes ({:.2}% smaller)", fesh_len as i64 - xz.len() as i64, saved),
+    ]
+}
+
+/// Where `got` first departs from `want`, with up to 8 bytes of each from there, or None if
+/// they are identical.
+pub(crate) fn first_difference(got: &[u8], want: &[u8]) -> Option<String> {
+    let at = match got.iter().zip(want).position(|(a, b)| a != b) {
+        Some(at) => at,
+        None if got.len() == want.len() => return None,
+        None => got.len().min(want.len()),
+    };
+    let hex = |b: &[u8]| b[at..b.len().min(at + 8)].iter().map(|x| format!("{:02x}", x)).collect::<Vec<_>>().join(" ");
+    Some(format!("first difference at offset {:#x} (decoded {} bytes, original {}): decoded [{}], original [{}]",
+        at, got.len(), want.len(), hex(got), hex(want)))
+}
+
+/// Compresses `data` without the round-trip check's untransformed fallback and decodes it without
+/// the file checksum, so a transform that mis-fires shows up as the bytes it got wrong.
+pub(crate) fn verify_round_trip(data: &[u8], opts: &CompressOptions) -> Result<(), String> {
+    let blob = compress_unwrapped(data, opts);
+    let c = parse_container(&blob, 0).map_err(|e| e.to_string())?;
+    let (decoded, _) = rebuild(c).map_err(|e| format!("decode failed: {}", e))?;
+    first_difference(&decoded, data).map_or(Ok(()), Err)
+}
+
+fn stdout_error(e: std::io::Error) -> CliError {
+    CliError::Io(format!("cannot write stdout: {}", e))
+}
+
+/// `println!` to `io`'s stdout that returns a `CliError` instead of panicking when it is closed
+/// (`| head`).
+macro_rules! out {
+    ($io:expr, $($arg:tt)*) => { writeln!($io.stdout, $($arg)*).map_err(stdout_error)? };
+}
+
+/// `compress_with`, warning on stderr when `name` only compressed as the untransformed fallback.
+fn compress_noting_fallback(name: &str, data: &[u8], opts: &CompressOptions) -> Vec<u8> {
+    let (blob, fell_back) = compress_reporting(data, opts);
+    if fell_back { eprintln!("fesh: {}: output failed its round-trip check; stored the input untransformed", name); }
+    blob
+}
+
+/// What a `-` path reads from and writes to: the process's stdin and stdout, or buffers in tests.
+pub(crate) struct Stdio<R, W> {
+    pub(crate) stdin: R,
+    pub(crate) stdout: W,
+}
+
+impl<R: Read, W: Write> Stdio<R, W> {
+    /// `-` reads all of stdin, so fesh can sit in a pipeline.
+    fn read_input(&mut self, path: &str) -> Result<Vec<u8>, CliError> {
+        if path == "-" {
+            let mut data = Vec::new();
+            self.stdin.read_to_end(&mut data).map_err(|e| CliError::Io(format!("cannot read stdin: {}", e)))?;
+            return Ok(data);
+        }
+        fs::read(path).map_err(|e| CliError::Io(format!("cannot read {}: {}", path, e)))
+    }
+
+    /// `-` writes to stdout.
+    fn write_output(&mut self, path: &str, data: &[u8]) -> Result<(), CliError> {
+        if path == "-" {
+            return self.stdout.write_all(data).and_then(|_| self.stdout.flush()).map_err(stdout_error);
+        }
+        fs::write(path, data).map_err(|e| CliError::Io(format!("cannot write {}: {}", path, e)))
+    }
+}
+
+fn output_arg(cli: &Cli) -> Result<&str, CliError> {
+    cli.positional.get(2).map(|s| s.as_str()).ok_or_else(|| CliError::Usage(USAGE.into()))
+}
+
+/// Decimal or `0x`-prefixed hex, as offsets are usually quoted from a hex dump.
+fn parse_size(flag: &str, v: &str) -> Result<usize, CliError> {
+    let parsed = match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => v.parse().ok(),
+    };
+    parsed.ok_or_else(|| CliError::Usage(format!("{} expects a byte count, got {}", flag, v)))
+}
+
+/// `--input-offset` / `--input-length`: the slice of a `len`-byte input to compress. The
+/// length defaults to the rest of the file.
+pub(crate) fn input_range(cli: &Cli, len: usize) -> Result<std::ops::Range<usize>, CliError> {
+    let start = cli.value("--input-offset").map(|v| parse_size("--input-offset", v)).transpose()?.unwrap_or(0);
+    let size = cli.value("--input-length").map(|v| parse_size("--input-length", v)).transpose()?.unwrap_or(len.saturating_sub(start));
+    match start.checked_add(size) {
+        Some(end) if end <= len => Ok(start..end),
+        _ => Err(CliError::Usage(format!("input range {:#x}+{:#x} is outside the {}-byte input", start, size, len))),
+    }
+}
+
+fn compress_options(cli: &Cli) -> Result<CompressOptions, CliError> {
+    let endian = match (cli.flag("--force-le"), cli.flag("--force-be")) {
+        (true, true) => return Err(CliError::Usage("--force-le and --force-be are mutually exclusive".into())),
+        (true, false) => Endian::Le,
+        (false, true) => Endian::Be,
+        (false, false) => Endian::Best,
+    };
+    let search = match cli.value("--search") {
+        None => None,
+        Some("full") => Some(Search::Full),
+        Some("fast") => Some(Search::Fast),
+        Some("single") => Some(Search::Single),
+        Some(other) => return Err(CliError::Usage(format!("--search expects fast, full or single, got {}", other))),
+    };
+    let level = |flag: &str, v: &str| parse_level(v).ok_or_else(|| CliError::Usage(format!("{} expects 0-9 or 0e-9e, got {}", flag, v)));
+    let global = match cli.value("--level") { Some(v) => level("--level", v)?, None => DEFAULT_PRESET };
+    let mut levels = [global; CAT_COUNT];
+    let mut set_by: [Option<String>; CAT_COUNT] = Default::default();
+    for (name, block) in LEVEL_BLOCKS {
+        let flag = format!("--level-{}", name);
+        let Some(v) = cli.value(&flag) else { continue };
+        let preset = level(&flag, v)?;
+        if let Some(prev) = &set_by[block] {
+            if levels[block] != preset {
+                return Err(CliError::Usage(format!("{} and {} set the same block to different levels", prev, flag)));
+            }
+        }
+        levels[block] = preset;
+        set_by[block] = Some(flag);
+    }
+    let zstd_range = zstd::compression_level_range();
+    let zstd = |flag: &str, v: &str| v.parse().ok().filter(|l| zstd_range.contains(l)).map(Backend::Zstd)
+        .ok_or_else(|| CliError::Usage(format!("{} expects a zstd level {} to {}, got {}", flag, zstd_range.start(), zstd_range.end(), v)));
+    let mut backends = [match cli.value("--zstd") { Some(v) => zstd("--zstd", v)?, None => Backend::Xz }; CAT_COUNT];
+    let mut set_by: [Option<String>; CAT_COUNT] = Default::default();
+    for (name, block) in LEVEL_BLOCKS {
+        let flag = format!("--zstd-{}", name);
+        let Some(v) = cli.value(&flag) else { continue };
+        let backend = zstd(&flag, v)?;
+        if let Some(prev) = &set_by[block] {
+            if backends[block] != backend {
+                return Err(CliError::Usage(format!("{} and {} set the same block to different levels", prev, flag)));
+            }
+        }
+        backends[block] = backend;
+        set_by[block] = Some(flag);
+    }
+    let parallel_code = match cli.value("--parallel-code") {
+        None => 0,
+        Some(v) => v.parse().ok().filter(|n| (1..=MAX_CODE_CHUNKS).contains(n))
+            .ok_or_else(|| CliError::Usage(format!("--parallel-code expects 1-{}, got {}", MAX_CODE_CHUNKS, v)))?,
+    };
+    let small_threshold = cli.value("--small-threshold").map(|v| parse_size("--small-threshold", v)).transpose()?.unwrap_or(SMALL_INPUT);
+    Ok(CompressOptions { stream_crc: cli.flag("--stream-crc"), endian, search, levels, normalize_build_id: cli.flag("--normalize-build-id"),
+        prime_streams: cli.flag("--prime-streams"), check_roundtrip: !cli.flag("--no-check"), mixed_endian: cli.flag("--mixed-endian"),
+        parallel_code, small_threshold, journal: None, max_threads: None, backends })
+}
+
+pub(crate) fn run(cli: &Cli) -> Result<(), CliError> {
+    run_with(cli, &mut Stdio { stdin: std::io::stdin(), stdout: std::io::stdout() })
+}
+
+/// `run` with `-` and the report lines going through `io`.
+pub(crate) fn run_with<R: Read, W: Write>(cli: &Cli, io: &mut Stdio<R, W>) -> Result<(), CliError> {
+    if cli.positional.len() < 2 { return Err(CliError::Usage(USAGE.into())); }
+    let cmd = &cli.positional[0];
+    let path = &cli.positional[1];
+    let quiet = cli.flag("--quiet");
+    if let Some(v) = cli.value("--threads") {
+        let n: usize = v.parse().ok().filter(|&n| n > 0).ok_or_else(|| CliError::Usage(format!("--threads expects a positive count, got {}", v)))?;
+        rayon::ThreadPoolBuilder::new().num_threads(n).build_global().map_err(|e| CliError::Usage(format!("--threads: {}", e)))?;
+    }
+
+    match cmd.as_str() {
+        "compare" => {
+            let data = io.read_input(path)?;
+            let start = Instant::now();
+            let (compressed, stats) = compress_with_stats(&data, &compress_options(cli)?);
+            let c_time = start.elapsed();
+            let start = Instant::now();
+            let (decompressed, layout) = decompress_with_layout(&compressed).map_err(|e| CliError::Decode(e.to_string()))?;
+            let d_time = start.elapsed();
+            if data != decompressed {
+                return Err(CliError::Mismatch(format!("round-trip mismatch on {}", path)));
+            }
+
+            let ratio = (compressed.len() as f64 / data.len() as f64) * 100.0;
+            if quiet {
+                out!(io, "{:.2}", ratio);
+                return Ok(());
+            }
+            out!(io, "====== FESH USASE vG (EH_FRAME_HDR + Jump Tables + LC0 MoE) ======");
+            out!(io, "Target File: {}", path);
+            out!(io, "Input:       {} bytes", data.len());
+            out!(io, "FESH (Rust): {} bytes ({:.2}%)", compressed.len(), ratio);
+            out!(io, "Comp Time:   {:?}", c_time);
+            out!(io, "Decomp Time: {:?}", d_time);
+            out!(io, "Layout:      {:?}, {} sections, image base {:#x}", layout.arch, layout.sections.len(), layout.image_base);
+            out!(io, "Byte order:  {}", if stats.big_endian { "big-endian fields" } else { "little-endian fields" });
+            out!(io, "{:<10} {:>10}", "part", "stored");
+            let blocks = stats.blocks.iter().enumerate().map(|(cat, &n)| (block_name(cat), n));
+            let parts = [("runs", stats.runs)].into_iter().chain(blocks).chain([("jt_meta", stats.jt_meta), ("other meta", stats.other_meta)]);
+            for (name, n) in parts.filter(|&(_, n)| n > 0) { out!(io, "{:<10} {:>10}", name, n); }
+            if cli.flag("--compare-xz") {
+                for line in compare_xz(&data, compressed.len()) { out!(io, "{}", line); }
+            }
+        }
+        "compress" => {
+            let out_path = output_arg(cli)?;
+            let mut input = io.read_input(path)?;
+            let range = input_range(cli, input.len())?;
+            input.truncate(range.end);
+            input.drain(..range.start);
+            let mut data = input.clone();
+            let excluded = cli.values("--exclude-section");
+            if out_path == "-" && ((!excluded.is_empty() && !cli.flag("--drop")) || cli.flag("--resumable")) {
+                return Err(CliError::Usage("writing to stdout leaves nowhere for <output>.excl or the journal; add --drop or drop --resumable".into()));
+            }
+            if !excluded.is_empty() {
+                let secs = exclude_sections(&mut data, &excluded).map_err(CliError::Usage)?;
+                if !cli.flag("--drop") {
+                    io.write_output(&format!("{}.excl", out_path), &write_excluded(&secs))?;
+                }
+            }
+            let mut opts = compress_options(cli)?;
+            let journal_path = format!("{}.journal", out_path);
+            if cli.flag("--resumable") {
+                let journal = BlockJournal::open(std::path::Path::new(&journal_path))
+                    .map_err(|e| CliError::Io(format!("cannot open {}: {}", journal_path, e)))?;
+                opts.journal = Some(std::sync::Arc::new(journal));
+            }
+            let blob = compress_noting_fallback(path, &data, &opts);
+            io.write_output(out_path, &blob)?;
+            if let Some(journal) = &opts.journal {
+                let resumed = journal.resumed.load(std::sync::atomic::Ordering::Relaxed);
+                if resumed > 0 && !quiet { eprintln!("fesh: resumed {} blocks from {}", resumed, journal_path); }
+                fs::remove_file(&journal_path).map_err(|e| CliError::Io(format!("cannot remove {}: {}", journal_path, e)))?;
+            }
+            if let Some(manifest) = cli.value("--manifest") {
+                let m = ManifestInput { input_path: path, input: &input, output_path: out_path, blob: &blob, opts: &opts, excluded: &excluded, range };
+                io.write_output(manifest, build_manifest(&m).map_err(|e| CliError::Decode(e.to_string()))?.as_bytes())?;
+            }
+        }
+        "decompress" => {
+            let out_path = output_arg(cli)?;
+            let data = io.read_input(path)?;
+            let mut out = decompress(&data).map_err(|e| CliError::Decode(e.to_string()))?;
+            if let Some(restore) = cli.value("--restore") {
+                let secs = read_excluded(&io.read_input(restore)?).map_err(|e| CliError::Decode(e.to_string()))?;
+                restore_excluded(&mut out, &secs).map_err(|e| CliError::Decode(e.to_string()))?;
+            }
+            io.write_output(out_path, &out)?;
+        }
+        "verify-against" => {
+            let original_path = output_arg(cli)?;
+            let original = io.read_input(original_path)?;
+            let decoded = decompress(&io.read_input(path)?).map_err(|e| CliError::Decode(e.to_string()))?;
+            if let Some(diff) = first_difference(&decoded, &original) {
+                return Err(CliError::Mismatch(format!("{} does not decode to {}: {}", path, original_path, diff)));
+            }
+            if !quiet { out!(io, "{}: ok ({} bytes match {})", path, original.len(), original_path); }
+        }
+        "verify" => {
+            let data = io.read_input(path)?;
+            let opts = CompressOptions { check_roundtrip: false, ..compress_options(cli)? };
+            verify_round_trip(&data, &opts).map_err(|why| CliError::Mismatch(format!("FAIL {}: {}", path, why)))?;
+            if !quiet { out!(io, "PASS {}", path); }
+        }
+        "info" => {
+            for line in blob_info(&io.read_input(path)?).map_err(CliError::Decode)? { out!(io, "{}", line); }
+        }
+        "analyze" => {
+            for line in analyze(&io.read_input(path)?) { out!(io, "{}", line); }
+        }
+        "explain" => {
+            for line in explain(&io.read_input(path)?) { out!(io, "{}", line); }
+        }
+        "verify-format" => {
+            let data = io.read_input(path)?;
+            let c = verify_format(&data, 0).map_err(|e| CliError::Decode(e.to_string()))?;
+            if !quiet {
+                let stored = c.blocks.iter().filter(|(_, p)| !p.is_empty()).count();
+                out!(io, "{}: ok (format v{}, {} stored blocks, {} bytes unpacked)", path, c.version, stored, c.orig_len);
+            }
+        }
+        "container-overhead" => {
+            let blob = compress_noting_fallback(path, &io.read_input(path)?, &compress_options(cli)?);
+            let report = container_overhead(&blob).map_err(|e| CliError::Decode(e.to_string()))?;
+            let framing: usize = report.iter().map(|b| b.framing).sum();
+            if quiet {
+                out!(io, "{}", framing);
+                return Ok(());
+            }
+            out!(io, "{:>5} {:>6} {:>10} {:>10} {:>8} {:>10}", "block", "method", "unpacked", "stored", "framing", "payload");
+            for b in &report {
+                let method = method_name(b.method);
+                out!(io, "{:>5} {:>6} {:>10} {:>10} {:>8} {:>10}", b.cat, method, b.unpacked, b.stored, b.framing, b.stored - b.framing);
+            }
+            out!(io, "Container framing: {} of {} bytes ({:.2}%)", framing, blob.len(), framing as f64 * 100.0 / blob.len().max(1) as f64);
+        }
+        "split" => {
+            let out_dir = output_arg(cli)?;
+            let blob = compress_unwrapped(&io.read_input(path)?, &compress_options(cli)?);
+            fs::create_dir_all(out_dir).map_err(|e| CliError::Io(format!("cannot create {}: {}", out_dir, e)))?;
+            for (name, bytes) in split_parts(&blob).map_err(|e| CliError::Decode(e.to_string()))? {
+                io.write_output(&format!("{}/{}", out_dir, name), &bytes)?;
+            }
+        }
+        "join" => {
+            let out_path = output_arg(cli)?;
+            let out = join_parts(|name| fs::read(format!("{}/{}", path, name)).ok()).map_err(|e| CliError::Decode(e.to_string()))?;
+            io.write_output(out_path, &out)?;
+        }
+        "archive" => {
+            let inputs = &cli.positional[2..];
+            if inputs.is_empty() { return Err(CliError::Usage(USAGE.into())); }
+            let opts = compress_options(cli)?;
+            let integrity = match cli.value("--integrity") {
+                None => None,
+                Some(v) => Some(INTEGRITY_SCHEMES.iter().find(|&&(_, name, _)| name == v).map(|&(id, _, _)| id)
+                    .ok_or_else(|| CliError::Usage(format!("--integrity expects crc32, crc64 or sha256, got {}", v)))?),
+            };
+            let mut members: Vec<(String, Vec<u8>)> = Vec::new();
+            for input in inputs {
+                let name = std::path::Path::new(input).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| input.clone());
+                if members.iter().any(|(n, _)| *n == name) {
+                    return Err(CliError::Usage(format!("duplicate archive member {}", name)));
+                }
+                members.push((name, compress_noting_fallback(input, &io.read_input(input)?, &opts)));
+            }
+            io.write_output(path, &write_archive(&members, cli.flag("--dedupe-streams"), integrity))?;
+        }
+        "extract" => {
+            let out_dir = output_arg(cli)?;
+            let members = read_archive(&io.read_input(path)?).map_err(|e| CliError::Decode(e.to_string()))?;
+            fs::create_dir_all(out_dir).map_err(|e| CliError::Io(format!("cannot create {}: {}", out_dir, e)))?;
+            for (name, blob) in &members {
+                if name.is_empty() || name.contains('/') || name.contains('\\') || name == "." || name == ".." {
+                    return Err(CliError::Decode(format!("unsafe archive member name {:?}", name)));
+                }
+                let out = decompress(blob).map_err(|e| CliError::Decode(format!("{}: {}", name, e)))?;
+                io.write_output(&format!("{}/{}", out_dir, name), &out)?;
+            }
+        }
+        "blob-diff" | "blob-patch" => {
+            let (second, out_path) = match &cli.positional[2..] {
+                [second, out] => (second, out),
+                _ => return Err(CliError::Usage(USAGE.into())),
+            };
+            let (old, second) = (io.read_input(path)?, io.read_input(second)?);
+            let out = if cmd == "blob-diff" { blob_diff(&old, &second) } else { blob_patch(&old, &second) };
+            io.write_output(out_path, &out.map_err(|e| CliError::Decode(e.to_string()))?)?;
+        }
+        "bench-corpus" => {
+            let dir = std::path::Path::new(path);
+            let files = corpus_files(dir).map_err(|e| CliError::Io(format!("cannot list {}: {}", path, e)))?;
+            let opts = compress_options(cli)?;
+            let rows = files.into_par_iter().map(|rel| {
+                let file = dir.join(&rel);
+                let data = fs::read(&file).map_err(|e| CliError::Io(format!("cannot read {}: {}", file.display(), e)))?;
+                Ok(bench_file(rel.to_string_lossy().into_owned(), &data, &opts))
+            }).collect::<Result<Vec<_>, CliError>>()?;
+            write!(io.stdout, "{}", bench_report(&rows, cli.flag("--csv"))).map_err(stdout_error)?;
+            let failed = rows.iter().filter(|r| r.status != BenchStatus::Ok).count();
+            if failed > 0 {
+                return Err(CliError::Mismatch(format!("{} of {} files failed round-trip", failed, rows.len())));
+            }
+        }
+        other => return Err(CliError::Usage(format!("unknown command {}\n{}", other, USAGE))),
+    }
+    Ok(())
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let result = Cli::parse(&args[1..]).map_err(CliError::Usage).and_then(|cli| run(&cli));
+    if let Err(e) = result {
+        eprintln!("fesh: {}", e.message());
+        std::process::exit(e.exit_code());
+    }
+}
@@ -1,1741 +1,3 @@
-use std::collections::HashMap;
-use byteorder::{ByteOrder, LittleEndian};
-use iced_x86::{Decoder, DecoderOptions};
-use object::{Architecture, Object, ObjectSection, ObjectSegment, SectionKind};
-use rayon::prelude::*;
-use std::fs;
-use std::io::{Read, Write};
-use std::time::Instant;
-use xz2::stream::{Check, Filters, LzmaOptions, Stream};
-
-const MAGIC: &[u8; 4] = b"FESv";
-const FORMAT_VERSION: u8 = 5;
-const FUSED_NUM_BLOCK_CAT: usize = CAT_GNUHASH as usize;
-const NUM_FUSED_ORDER: [usize; 12] = [CAT_S2 as usize, CAT_S4 as usize, CAT_S8 as usize, CAT_RELR8 as usize, CAT_S16 as usize, CAT_REL16 as usize, CAT_DYNAMIC16 as usize, CAT_S24 as usize, CAT_RELA24 as usize, CAT_SYM24 as usize, CAT_JT4 as usize, CAT_GNUHASH as usize];
-
-const FUSED_TXT_BLOCK_CAT: usize = CAT_OTHER as usize;
-const TXT_FUSED_ORDER: [usize; 2] = [CAT_STR as usize, CAT_OTHER as usize];
-
-const CAT_OTHER: u8 = 0;
-const CAT_CODE: u8 = 1;
-const CAT_STR: u8 = 2;
-const CAT_S2: u8 = 3;
-const CAT_S4: u8 = 4;
-const CAT_S8: u8 = 5;
-const CAT_RELR8: u8 = 6;
-const CAT_S16: u8 = 7;
-const CAT_REL16: u8 = 8;
-const CAT_DYNAMIC16: u8 = 9;
-const CAT_S24: u8 = 10;
-const CAT_RELA24: u8 = 11;
-const CAT_SYM24: u8 = 12;
-const CAT_EH: u8 = 13;
-const CAT_JT4: u8 = 14;
-const CAT_GNUHASH: u8 = 15;
-const CAT_COUNT: usize = 16;
-
-const XZ_CHECK: Check = Check::None;
-const PRESET_EXTREME: u32 = 1u32 << 31;
-
-fn choose_pb(cat: usize) -> u32 {
-    match cat {
-        c if c == CAT_CODE as usize => 2,
-        c if c == CAT_EH as usize => 2,
-        c if c == CAT_OTHER as usize => 2,
-        _ => 0, // All numeric / transposed streams benefit from pb=0
-    }
-}
-
-fn choose_dict_size(stream_len: usize) -> u32 {
-    let min_ds: usize = 1 << 16;
-    let max_ds: usize = 1 << 26;
-    let mut ds = stream_len.max(min_ds);
-    ds = ds.next_power_of_two();
-    ds = ds.clamp(min_ds, max_ds);
-    ds as u32
-}
-
-fn compress_xz_tuned(data: &[u8], preset: u32, pb: u32, dict_size: u32) -> Vec<u8> {
-    if data.is_empty() { return Vec::new(); }
-    let mut opts = LzmaOptions::new_preset(preset).expect("bad preset");
-    opts.position_bits(pb).dict_size(dict_size);
-    let mut filters = Filters::new();
-    filters.lzma2(&opts);
-    let stream = Stream::new_stream_encoder(&filters, XZ_CHECK).expect("xz encoder");
-    let mut enc = xz2::write::XzEncoder::new_stream(Vec::new(), stream);
-    enc.write_all(data).unwrap();
-    enc.finish().unwrap()
-}
-
-fn decompress_xz(data: &[u8]) -> Result<Vec<u8>, String> {
-    if data.is_empty() { return Ok(Vec::new()); }
-    let mut decoder = xz2::read::XzDecoder::new(data);
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out).map_err(|e| e.to_string())?;
-    Ok(out)
-}
-
-
-#[derive(Clone)]
-struct Block {
-    method: u8,
-    payload: Vec<u8>,
-}
-
-fn write_block(out: &mut Vec<u8>, method: u8, payload: &[u8]) {
-    let tag = ((payload.len() as u64) << 1) | ((method as u64) & 1);
-    write_varint(out, tag);
-    out.extend_from_slice(payload);
-}
-
-
-#[inline(always)]
-fn unzigzag64(z: u64) -> i64 {
-    ((z >> 1) as i64) ^ (-((z & 1) as i64))
-}
-#[inline(always)]
-fn unzigzag32(z: u32) -> i32 {
-    ((z >> 1) as i32) ^ (-((z & 1) as i32))
-}
-
-fn read_block<'a>(data: &'a [u8], pos: &mut usize) -> Result<(u8, &'a [u8]), String> {
-    let tag = read_varint(data, pos)?;
-    let method = (tag & 1) as u8;
-    let len = (tag >> 1) as usize;
-    if *pos + len > data.len() { return Err("block out of range".into()); }
-    let slice = &data[*pos..*pos + len];
-    *pos += len;
-    Ok((method, slice))
-}
-
-fn write_varint(buf: &mut Vec<u8>, mut val: u64) {
-    loop {
-        let mut byte = (val & 0x7F) as u8;
-        val >>= 7;
-        if val != 0 {
-            byte |= 0x80;
-            buf.push(byte);
-        } else {
-            buf.push(byte);
-            break;
-        }
-    }
-}
-
-fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, String> {
-    let mut val = 0u64;
-    let mut shift: u32 = 0;
-    loop {
-        if *pos >= buf.len() { return Err("varint eof".into()); }
-        let byte = buf[*pos];
-        *pos += 1;
-        val |= ((byte & 0x7F) as u64) << shift;
-        if (byte & 0x80) == 0 { return Ok(val); }
-        shift += 7;
-        if shift >= 64 { return Err("varint overflow".into()); }
-    }
-}
-
-fn shuffle_bytes(data: &[u8], stride: usize) -> Vec<u8> {
-    if data.is_empty() || stride <= 1 { return data.to_vec(); }
-    let mut out = vec![0u8; data.len()];
-    let count = data.len() / stride;
-    let end = count * stride;
-    for i in 0..count {
-        for j in 0..stride {
-            out[j * count + i] = data[i * stride + j];
-        }
-    }
-    for i in end..data.len() { out[i] = data[i]; }
-    out
-}
-
-fn unshuffle_bytes(data: &[u8], stride: usize) -> Vec<u8> {
-    if data.is_empty() || stride <= 1 { return data.to_vec(); }
-    let mut out = vec![0u8; data.len()];
-    let count = data.len() / stride;
-    let end = count * stride;
-    for i in 0..count {
-        for j in 0..stride {
-            out[i * stride + j] = data[j * count + i];
-        }
-    }
-    for i in end..data.len() { out[i] = data[i]; }
-    out
-}
-
-fn bswap_u32_array(data: &mut [u8]) {
-    for chunk in data.chunks_exact_mut(4) {
-        let val = LittleEndian::read_u32(chunk);
-        LittleEndian::write_u32(chunk, val.swap_bytes());
-    }
-}
-
-fn bswap_u64_array(data: &mut [u8]) {
-    for chunk in data.chunks_exact_mut(8) {
-        let val = LittleEndian::read_u64(chunk);
-        LittleEndian::write_u64(chunk, val.swap_bytes());
-    }
-}
-
-fn bswap_cat(data: &mut [u8], cat: usize) {
-    match cat {
-        c if c == CAT_S4 as usize => bswap_u32_array(data),
-        c if c == CAT_JT4 as usize => bswap_u32_array(data),
-        c if c == CAT_S8 as usize => bswap_u64_array(data),
-        c if c == CAT_RELR8 as usize => bswap_u64_array(data),
-        c if c == CAT_S16 as usize => {
-            for chunk in data.chunks_exact_mut(16) {
-                let v1 = LittleEndian::read_u64(&chunk[0..8]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                LittleEndian::write_u64(&mut chunk[0..8], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-            }
-        },
-        c if c == CAT_REL16 as usize => {
-            for chunk in data.chunks_exact_mut(16) {
-                let v1 = LittleEndian::read_u64(&chunk[0..8]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                LittleEndian::write_u64(&mut chunk[0..8], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-            }
-        },
-        c if c == CAT_DYNAMIC16 as usize => {
-            for chunk in data.chunks_exact_mut(16) {
-                let v1 = LittleEndian::read_u64(&chunk[0..8]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                LittleEndian::write_u64(&mut chunk[0..8], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-            }
-        },
-        c if c == CAT_S24 as usize => {
-            for chunk in data.chunks_exact_mut(24) {
-                let v1 = LittleEndian::read_u64(&chunk[0..8]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                let v3 = LittleEndian::read_u64(&chunk[16..24]);
-                LittleEndian::write_u64(&mut chunk[0..8], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[16..24], v3.swap_bytes());
-            }
-        },
-        c if c == CAT_RELA24 as usize => {
-            for chunk in data.chunks_exact_mut(24) {
-                let v1 = LittleEndian::read_u64(&chunk[0..8]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                let v3 = LittleEndian::read_u64(&chunk[16..24]);
-                LittleEndian::write_u64(&mut chunk[0..8], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[16..24], v3.swap_bytes());
-            }
-        },
-        c if c == CAT_SYM24 as usize => {
-            for chunk in data.chunks_exact_mut(24) {
-                let v1 = LittleEndian::read_u32(&chunk[0..4]);
-                let v2 = LittleEndian::read_u64(&chunk[8..16]);
-                let v3 = LittleEndian::read_u64(&chunk[16..24]);
-                LittleEndian::write_u32(&mut chunk[0..4], v1.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[8..16], v2.swap_bytes());
-                LittleEndian::write_u64(&mut chunk[16..24], v3.swap_bytes());
-            }
-        },
-        _ => {}
-    }
-}
-
-// ---------------- Struct Delta Typed Processing ----------------
-
-fn process_elf_tables(file_data: &[u8], is_compress: bool) -> Vec<u8> {
-    let mut out = file_data.to_vec();
-    let obj = match object::File::parse(file_data) {
-        Ok(o) => o,
-        Err(_) => return out,
-    };
-    if obj.architecture() != Architecture::X86_64 || !obj.is_little_endian() || !obj.is_64() {
-        return out;
-    }
-
-    for sec in obj.sections() {
-        let name = sec.name().unwrap_or("");
-        let (file_off, size) = match sec.file_range() {
-            Some(r) => r,
-            None => continue,
-        };
-        let file_off = file_off as usize;
-        let size = size as usize;
-        if file_off + size > out.len() { continue; }
-        
-        let slice = &mut out[file_off .. file_off + size];
-
-        if name.starts_with(".rela") {
-            transform_rela24(slice, is_compress);
-        } else if name.starts_with(".rel") && !name.starts_with(".relr") {
-            transform_rel16(slice, is_compress);
-        } else if name == ".dynsym" || name == ".symtab" {
-            transform_sym24(slice, is_compress);
-        } else if name.starts_with(".relr") {
-            transform_relr8(slice, is_compress);
-        } else if name == ".dynamic" {
-            transform_dynamic16(slice, is_compress);
-        } else if name == ".gnu.hash" {
-            transform_gnuhash(slice, is_compress);
-        }
-    }
-    out
-}
-
-fn transform_rela24(buf: &mut [u8], is_compress: bool) {
-    if buf.len() % 24 != 0 { return; }
-    let n = buf.len() / 24;
-    let mut prev_off: u64 = 0;
-    let mut prev_sym: u32 = 0;
-    let mut prev_add: i64 = 0;
-
-    for i in 0..n {
-        let p = i * 24;
-        let off = LittleEndian::read_u64(&buf[p..p + 8]);
-        let info = LittleEndian::read_u64(&buf[p + 8..p + 16]);
-        let add = LittleEndian::read_i64(&buf[p + 16..p + 24]);
-        let sym = (info >> 32) as u32;
-        let typ = (info & 0xFFFF_FFFF) as u32;
-
-        if is_compress {
-            let off_d = if i == 0 { off } else { off.wrapping_sub(prev_off) };
-            let sym_d = if i == 0 { sym } else { sym.wrapping_sub(prev_sym) };
-            let add_d = if i == 0 { add } else { add.wrapping_sub(prev_add) };
-
-            let zz_off = ((off_d as i64) << 1) ^ ((off_d as i64) >> 63);
-            let zz_sym = ((sym_d as i32) << 1) ^ ((sym_d as i32) >> 31);
-            
-            LittleEndian::write_u64(&mut buf[p..p + 8], zz_off as u64);
-            let info2 = ((zz_sym as u64) << 32) | (typ as u64);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], info2);
-            let zz_add = ((add_d << 1) ^ (add_d >> 63)) as u64;
-            LittleEndian::write_u64(&mut buf[p + 16..p + 24], zz_add);
-
-            prev_off = off;
-            prev_sym = sym;
-            prev_add = add;
-        } else {
-            let off_d = unzigzag64(off) as u64;
-            let off_v = if i == 0 { off_d } else { prev_off.wrapping_add(off_d) };
-            
-            let sym_d = unzigzag32((info >> 32) as u32) as u32;
-            let sym_v = if i == 0 { sym_d } else { prev_sym.wrapping_add(sym_d) };
-            
-            let add_d = unzigzag64(add as u64);
-            let add_v = if i == 0 { add_d } else { prev_add.wrapping_add(add_d) };
-
-            LittleEndian::write_u64(&mut buf[p..p + 8], off_v);
-            let info2 = ((sym_v as u64) << 32) | (typ as u64);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], info2);
-            LittleEndian::write_i64(&mut buf[p + 16..p + 24], add_v);
-
-            prev_off = off_v;
-            prev_sym = sym_v;
-            prev_add = add_v;
-        }
-    }
-}
-
-fn transform_rel16(buf: &mut [u8], is_compress: bool) {
-    if buf.len() % 16 != 0 { return; }
-    let n = buf.len() / 16;
-    let mut prev_off: u64 = 0;
-    let mut prev_sym: u32 = 0;
-
-    for i in 0..n {
-        let p = i * 16;
-        let off = LittleEndian::read_u64(&buf[p..p + 8]);
-        let info = LittleEndian::read_u64(&buf[p + 8..p + 16]);
-        let sym = (info >> 32) as u32;
-        let typ = (info & 0xFFFF_FFFF) as u32;
-
-        if is_compress {
-            let off_d = if i == 0 { off } else { off.wrapping_sub(prev_off) };
-            let sym_d = if i == 0 { sym } else { sym.wrapping_sub(prev_sym) };
-            
-            let zz_off = ((off_d as i64) << 1) ^ ((off_d as i64) >> 63);
-            let zz_sym = ((sym_d as i32) << 1) ^ ((sym_d as i32) >> 31);
-
-            LittleEndian::write_u64(&mut buf[p..p + 8], zz_off as u64);
-            let info2 = ((zz_sym as u64) << 32) | (typ as u64);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], info2);
-
-            prev_off = off;
-            prev_sym = sym;
-        } else {
-            let off_d = unzigzag64(off) as u64;
-            let off_v = if i == 0 { off_d } else { prev_off.wrapping_add(off_d) };
-            
-            let sym_d = unzigzag32((info >> 32) as u32) as u32;
-            let sym_v = if i == 0 { sym_d } else { prev_sym.wrapping_add(sym_d) };
-
-            LittleEndian::write_u64(&mut buf[p..p + 8], off_v);
-            let info2 = ((sym_v as u64) << 32) | (typ as u64);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], info2);
-
-            prev_off = off_v;
-            prev_sym = sym_v;
-        }
-    }
-}
-
-fn transform_sym24(buf: &mut [u8], is_compress: bool) {
-    if buf.len() % 24 != 0 { return; }
-    let n = buf.len() / 24;
-    let mut prev_name: u32 = 0;
-    let mut prev_val: u64 = 0;
-    let mut prev_sz: u64 = 0;
-
-    for i in 0..n {
-        let p = i * 24;
-        let name = LittleEndian::read_u32(&buf[p..p + 4]);
-        let val = LittleEndian::read_u64(&buf[p + 8..p + 16]);
-        let sz = LittleEndian::read_u64(&buf[p + 16..p + 24]);
-
-        if is_compress {
-            let name_d = if i == 0 { name } else { name.wrapping_sub(prev_name) };
-            let val_d  = if i == 0 { val } else { val.wrapping_sub(prev_val) };
-            let sz_d   = if i == 0 { sz } else { sz.wrapping_sub(prev_sz) };
-            
-            let zz_name = ((name_d as i32) << 1) ^ ((name_d as i32) >> 31);
-            let zz_val = ((val_d as i64) << 1) ^ ((val_d as i64) >> 63);
-            let zz_sz = ((sz_d as i64) << 1) ^ ((sz_d as i64) >> 63);
-
-            LittleEndian::write_u32(&mut buf[p..p + 4], zz_name as u32);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], zz_val as u64);
-            LittleEndian::write_u64(&mut buf[p + 16..p + 24], zz_sz as u64);
-
-            prev_name = name;
-            prev_val = val;
-            prev_sz = sz;
-        } else {
-            let name_d = unzigzag32(name) as u32;
-            let name_v = if i == 0 { name_d } else { prev_name.wrapping_add(name_d) };
-            
-            let val_d = unzigzag64(val) as u64;
-            let val_v  = if i == 0 { val_d } else { prev_val.wrapping_add(val_d) };
-            
-            let sz_d = unzigzag64(sz) as u64;
-            let sz_v   = if i == 0 { sz_d } else { prev_sz.wrapping_add(sz_d) };
-
-            LittleEndian::write_u32(&mut buf[p..p + 4], name_v);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], val_v);
-            LittleEndian::write_u64(&mut buf[p + 16..p + 24], sz_v);
-
-            prev_name = name_v;
-            prev_val = val_v;
-            prev_sz = sz_v;
-        }
-    }
-}
-
-fn transform_relr8(buf: &mut [u8], is_compress: bool) {
-    if buf.len() % 8 != 0 { return; }
-    let n = buf.len() / 8;
-    let mut prev_base = 0u64;
-
-    for i in 0..n {
-        let p = i * 8;
-        let val = LittleEndian::read_u64(&buf[p..p + 8]);
-
-        if (val & 1) == 0 { 
-            if is_compress {
-                let delta = if i == 0 { val } else { val.wrapping_sub(prev_base) };
-                LittleEndian::write_u64(&mut buf[p..p + 8], delta);
-                prev_base = val;
-            } else {
-                let base = if i == 0 { val } else { prev_base.wrapping_add(val) };
-                LittleEndian::write_u64(&mut buf[p..p + 8], base);
-                prev_base = base;
-            }
-        }
-    }
-}
-
-
-fn transform_gnuhash(buf: &mut [u8], is_compress: bool) {
-    if buf.len() < 16 { return; }
-    
-    let nbuckets = LittleEndian::read_u32(&buf[0..4]) as usize;
-    let maskwords = LittleEndian::read_u32(&buf[8..12]) as usize;
-    
-    let header_end = 16usize;
-    let bloom_bytes = match maskwords.checked_mul(8) { Some(x) => x, None => return };
-    let buckets_bytes = match nbuckets.checked_mul(4) { Some(x) => x, None => return };
-    
-    let bloom_end = match header_end.checked_add(bloom_bytes) { Some(x) => x, None => return };
-    let bucket_end = match bloom_end.checked_add(buckets_bytes) { Some(x) => x, None => return };
-    let max_bound = buf.len();
-    
-    let bl_e = bloom_end.min(max_bound);
-    let bu_e = bucket_end.min(max_bound);
-    
-    if header_end < bl_e {
-        if is_compress {
-            bswap_u64_array(&mut buf[header_end..bl_e]);
-            let s = shuffle_bytes(&buf[header_end..bl_e], 8);
-            buf[header_end..bl_e].copy_from_slice(&s);
-        } else {
-            let s = unshuffle_bytes(&buf[header_end..bl_e], 8);
-            buf[header_end..bl_e].copy_from_slice(&s);
-            bswap_u64_array(&mut buf[header_end..bl_e]);
-        }
-    }
-    if bl_e < bu_e {
-        if is_compress {
-            bswap_u32_array(&mut buf[bl_e..bu_e]);
-            let s = shuffle_bytes(&buf[bl_e..bu_e], 4);
-            buf[bl_e..bu_e].copy_from_slice(&s);
-        } else {
-            let s = unshuffle_bytes(&buf[bl_e..bu_e], 4);
-            buf[bl_e..bu_e].copy_from_slice(&s);
-            bswap_u32_array(&mut buf[bl_e..bu_e]);
-        }
-    }
-    if bu_e < max_bound {
-        if is_compress {
-            bswap_u32_array(&mut buf[bu_e..max_bound]);
-            let s = shuffle_bytes(&buf[bu_e..max_bound], 4);
-            buf[bu_e..max_bound].copy_from_slice(&s);
-        } else {
-            let s = unshuffle_bytes(&buf[bu_e..max_bound], 4);
-            buf[bu_e..max_bound].copy_from_slice(&s);
-            bswap_u32_array(&mut buf[bu_e..max_bound]);
-        }
-    }
-}
-
-fn transform_dynamic16(buf: &mut [u8], is_compress: bool) {
-    if buf.len() % 16 != 0 { return; }
-    let n = buf.len() / 16;
-    let mut prev_tag: u64 = 0;
-    let mut prev_val: u64 = 0;
-
-    for i in 0..n {
-        let p = i * 16;
-        let tag = LittleEndian::read_u64(&buf[p..p + 8]);
-        let val = LittleEndian::read_u64(&buf[p + 8..p + 16]);
-
-        if is_compress {
-            let tag_d = if i == 0 { tag } else { tag.wrapping_sub(prev_tag) };
-            let val_d = if i == 0 { val } else { val.wrapping_sub(prev_val) };
-            
-            let zz_tag = ((tag_d as i64) << 1) ^ ((tag_d as i64) >> 63);
-            let zz_val = ((val_d as i64) << 1) ^ ((val_d as i64) >> 63);
-
-            LittleEndian::write_u64(&mut buf[p..p + 8], zz_tag as u64);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], zz_val as u64);
-
-            prev_tag = tag;
-            prev_val = val;
-        } else {
-            let tag_d = unzigzag64(tag) as u64;
-            let tag_v = if i == 0 { tag_d } else { prev_tag.wrapping_add(tag_d) };
-            
-            let val_d = unzigzag64(val) as u64;
-            let val_v = if i == 0 { val_d } else { prev_val.wrapping_add(val_d) };
-
-            LittleEndian::write_u64(&mut buf[p..p + 8], tag_v);
-            LittleEndian::write_u64(&mut buf[p + 8..p + 16], val_v);
-
-            prev_tag = tag_v;
-            prev_val = val_v;
-        }
-    }
-}
-
-// ---------------- Jump Table Discovery ----------------
-#[derive(Debug, Clone, Copy)]
-struct JumpTable {
-    fo: usize,
-    count: usize,
-    mode: u8,
-}
-
-fn process_jump_tables(
-    file_data: &[u8],
-    is_compress: bool,
-    use_be: bool,
-    jt_meta_in: Option<&[u8]>,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<JumpTable>), String> {
-    let mut out = file_data.to_vec();
-    let obj = match object::File::parse(file_data) {
-        Ok(o) => o,
-        Err(_) => return Ok((out, Vec::new(), Vec::new())),
-    };
-
-    if obj.architecture() != Architecture::X86_64 {
-        return Ok((out, Vec::new(), Vec::new()));
-    }
-
-    let mut image_base = u64::MAX;
-    for seg in obj.segments() {
-        if seg.address() < image_base {
-            image_base = seg.address();
-        }
-    }
-    if image_base == u64::MAX {
-        image_base = 0;
-    }
-
-    let mut text_va = 0u64;
-    let mut text_size = 0u64;
-    for sec in obj.sections() {
-        if sec.name().unwrap_or("") == ".text" {
-            text_va = sec.address();
-            text_size = sec.size();
-            break;
-        }
-    }
-    if text_size == 0 {
-        return Ok((out, Vec::new(), Vec::new()));
-    }
-    let text_end = text_va.wrapping_add(text_size);
-
-    #[inline(always)]
-    fn zigzag32(v: i32) -> u32 {
-        ((v << 1) ^ (v >> 31)) as u32
-    }
-
-
-    #[inline(always)]
-    fn jt_score_bytes(v: u32, use_be: bool) -> [u8; 4] {
-        if use_be { v.to_le_bytes() } else { v.to_be_bytes() }
-    }
-
-    fn score_table_mode(
-        sec_data: &[u8],
-        run_start: usize,
-        run_count: usize,
-        sec_va: u64,
-        text_va: u64,
-        text_end: u64,
-        image_base: u64,
-        use_be: bool,
-        mode: u8,
-    ) -> Option<u64> {
-        let anchor_is_base = (mode & 0x01) != 0;
-        let use_delta = (mode & 0x02) != 0;
-
-        let base_va = sec_va.wrapping_add(run_start as u64);
-
-        let mut prev_lane = [0u8; 4];
-        let mut have_prev_lane = false;
-
-        let mut prev_norm: u32 = 0;
-        let mut have_prev_norm = false;
-
-        let mut score: u64 = 0;
-
-        for idx in 0..run_count {
-            let off = run_start + idx * 4;
-            if off + 4 > sec_data.len() {
-                return None;
-            }
-
-            let rel = LittleEndian::read_i32(&sec_data[off..off + 4]);
-            let entry_va = sec_va.wrapping_add(off as u64);
-            let anchor_va = if anchor_is_base { base_va } else { entry_va };
-
-            let target_va = anchor_va.wrapping_add(rel as i64 as u64);
-            if target_va < text_va || target_va >= text_end {
-                return None;
-            }
-
-            let norm = target_va.wrapping_sub(image_base) as u32;
-
-            let enc = if use_delta {
-                if !have_prev_norm {
-                    have_prev_norm = true;
-                    prev_norm = norm;
-                    norm
-                } else {
-                    let diff = norm.wrapping_sub(prev_norm) as i32;
-                    prev_norm = norm;
-                    zigzag32(diff)
-                }
-            } else {
-                norm
-            };
-
-            let lane = jt_score_bytes(enc, use_be);
-
-            if have_prev_lane {
-                for j in 0..4 {
-                    if lane[j] != prev_lane[j] {
-                        score += 1;
-                    }
-                }
-            } else {
-                have_prev_lane = true;
-            }
-            prev_lane = lane;
-        }
-
-        Some(score)
-    }
-
-    let mut tables: Vec<JumpTable> = Vec::new();
-
-    if is_compress {
-        const MIN_RUN: usize = 4;
-
-        for sec in obj.sections() {
-            let name = sec.name().unwrap_or("");
-            if name != ".rodata" && name != ".data.rel.ro" {
-                continue;
-            }
-
-            let (file_off_u64, sec_size_u64) = match sec.file_range() {
-                Some(r) => r,
-                None => continue,
-            };
-            let file_off = file_off_u64 as usize;
-
-            let data = match sec.data() {
-                Ok(d) => d,
-                Err(_) => continue,
-            };
-            if data.len() != sec_size_u64 as usize {
-                continue;
-            }
-
-            let sec_va = sec.address();
-
-            let mut run_start = 0usize;
-            let mut run_len = 0usize;
-
-            for i in (0..data.len().saturating_sub(3)).step_by(4) {
-                let rel = LittleEndian::read_i32(&data[i..i + 4]);
-                let entry_va = sec_va.wrapping_add(i as u64);
-                let target_va = entry_va.wrapping_add(rel as i64 as u64);
-
-                if target_va >= text_va && target_va < text_end {
-                    if run_len == 0 {
-                        run_start = i;
-                    }
-                    run_len += 1;
-                } else {
-                    if run_len >= MIN_RUN {
-                        let mut best_mode: u8 = 0;
-                        let mut best_score: u64 = u64::MAX;
-
-                        for mode in 0u8..4u8 {
-                            if let Some(s) = score_table_mode(
-                                data, run_start, run_len, sec_va, text_va, text_end,
-                                image_base, use_be, mode,
-                            ) {
-                                if s < best_score {
-                                    best_score = s;
-                                    best_mode = mode;
-                                }
-                            }
-                        }
-
-                        tables.push(JumpTable {
-                            fo: file_off + run_start,
-                            count: run_len,
-                            mode: best_mode,
-                        });
-                    }
-                    run_len = 0;
-                }
-            }
-
-            if run_len >= MIN_RUN {
-                let mut best_mode: u8 = 0;
-                let mut best_score: u64 = u64::MAX;
-
-                for mode in 0u8..4u8 {
-                    if let Some(s) = score_table_mode(
-                        data, run_start, run_len, sec_va, text_va, text_end,
-                        image_base, use_be, mode,
-                    ) {
-                        if s < best_score {
-                            best_score = s;
-                            best_mode = mode;
-                        }
-                    }
-                }
-
-                tables.push(JumpTable {
-                    fo: file_off + run_start,
-                    count: run_len,
-                    mode: best_mode,
-                });
-            }
-        }
-    } else {
-        let meta = jt_meta_in.unwrap_or(&[]);
-        let mut pos = 0usize;
-
-        let num_tables = match read_varint(meta, &mut pos) {
-            Ok(v) => v as usize,
-            Err(_) => return Ok((out, Vec::new(), Vec::new())),
-        };
-
-        let mut prev_fo = 0usize;
-        for _ in 0..num_tables {
-            let delta_fo = read_varint(meta, &mut pos)? as usize;
-            let packed = read_varint(meta, &mut pos)? as u64;
-
-            let fo = prev_fo + delta_fo;
-            prev_fo = fo;
-
-            let mode = (packed & 3) as u8;
-            let count = (packed >> 2) as usize;
-
-            tables.push(JumpTable { fo, count, mode });
-        }
-    }
-
-    let mut meta_out = Vec::new();
-    if is_compress {
-        write_varint(&mut meta_out, tables.len() as u64);
-        let mut prev_fo = 0usize;
-        let mut mode_counts = [0; 4];
-        for t in &tables {
-            write_varint(&mut meta_out, (t.fo - prev_fo) as u64);
-            let packed = ((t.count as u64) << 2) | ((t.mode as u64) & 3);
-            write_varint(&mut meta_out, packed);
-            prev_fo = t.fo;
-            mode_counts[t.mode as usize] += t.count;
-        }
-        // Just print counts on the largest execution branch for debugging
-        if mode_counts.iter().sum::<usize>() > 1000 {
-            println!("JT Mode Distribution [ENTRY_ABS, BASE_ABS, ENTRY_DEL, BASE_DEL]: {:?}", mode_counts);
-        }
-    }
-
-    let file_to_va = |offset: u64| -> Option<u64> {
-        for sec in obj.sections() {
-            if let Some((fo, size)) = sec.file_range() {
-                if offset >= fo && offset < fo + size {
-                    return Some(sec.address() + (offset - fo));
-                }
-            }
-        }
-        None
-    };
-
-    for t in &tables {
-        let anchor_is_base = (t.mode & 0x01) != 0;
-        let use_delta = (t.mode & 0x02) != 0;
-
-        let base_va = file_to_va(t.fo as u64).unwrap_or(0);
-
-        let mut prev_norm: u32 = 0;
-        let mut have_prev_norm = false;
-
-        for i in 0..t.count {
-            let p = t.fo + (i * 4);
-            if p + 4 > out.len() {
-                continue;
-            }
-
-            let entry_va = file_to_va(p as u64).unwrap_or(0);
-            let anchor_va = if anchor_is_base { base_va } else { entry_va };
-
-            if is_compress {
-                let rel = LittleEndian::read_i32(&out[p..p + 4]);
-                let norm = anchor_va
-                    .wrapping_add(rel as i64 as u64)
-                    .wrapping_sub(image_base) as u32;
-
-                let enc = if use_delta {
-                    if !have_prev_norm {
-                        have_prev_norm = true;
-                        prev_norm = norm;
-                        norm
-                    } else {
-                        let diff = norm.wrapping_sub(prev_norm) as i32;
-                        prev_norm = norm;
-                        zigzag32(diff)
-                    }
-                } else {
-                    norm
-                };
-
-                if use_be { out[p..p + 4].copy_from_slice(&enc.to_be_bytes()); } 
-                else { out[p..p + 4].copy_from_slice(&enc.to_le_bytes()); }
-            } else {
-                let enc = if use_be { u32::from_be_bytes(out[p..p + 4].try_into().unwrap()) } 
-                else { LittleEndian::read_u32(&out[p..p + 4]) };
-
-                let norm = if use_delta {
-                    if !have_prev_norm {
-                        have_prev_norm = true;
-                        prev_norm = enc;
-                        enc
-                    } else {
-                        let diff = unzigzag32(enc) as u32;
-                        let v = prev_norm.wrapping_add(diff);
-                        prev_norm = v;
-                        v
-                    }
-                } else {
-                    enc
-                };
-
-                let target_va = (norm as u64).wrapping_add(image_base);
-                let orig_rel = target_va.wrapping_sub(anchor_va) as u32;
-                LittleEndian::write_u32(&mut out[p..p + 4], orig_rel);
-            }
-        }
-    }
-
-    Ok((out, meta_out, tables))
-}
-
-
-
-// ---------------- EH Frame PC-Rel Normalization ----------------
-
-
-fn eh_pe_fixed_size(enc: u8, ptr_size: usize) -> Option<usize> {
-    if enc == 0xFF { return Some(0); } // DW_EH_PE_omit
-    match enc & 0x0F {
-        0x00 => Some(ptr_size),      
-        0x02 | 0x0A => Some(2),      
-        0x03 | 0x0B => Some(4),      
-        0x04 | 0x0C => Some(8),      
-        _ => None,                   
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-struct EhPatch {
-    fo: usize,
-    field_va: u64,
-}
-
-fn process_eh_frame_hdr(file_data: &[u8], is_compress: bool, use_be: bool) -> Vec<u8> {
-    let mut out = file_data.to_vec();
-    let obj = match object::File::parse(file_data) {
-        Ok(o) => o,
-        Err(_) => return out,
-    };
-    
-    let mut image_base = u64::MAX;
-    for sec in obj.segments() {
-        if sec.address() < image_base { image_base = sec.address(); }
-    }
-    if image_base == u64::MAX { image_base = 0; }
-
-    let mut patches = Vec::new();
-
-    for sec in obj.sections() {
-        if sec.name().unwrap_or("") != ".eh_frame_hdr" { continue; }
-        
-        let (file_off, sec_size) = match sec.file_range() { Some(r) => r, None => continue };
-        let file_off = file_off as usize;
-        let data = match sec.data() { Ok(d) => d, Err(_) => continue };
-        if data.len() != sec_size as usize || data.len() < 8 { continue; }
-        
-        let version = data[0];
-        let eh_frame_ptr_enc = data[1];
-        let fde_count_enc = data[2];
-        let table_enc = data[3];
-
-        if version != 1 { continue; }
-        if table_enc != 0x1b && table_enc != 0x3b { continue; }
-
-        let mut pos = 4;
-        let skip_sz = match eh_pe_fixed_size(eh_frame_ptr_enc, 8) {
-            Some(sz) => sz,
-            None => continue,
-        };
-        
-        if skip_sz == 4 && (eh_frame_ptr_enc == 0x1b || eh_frame_ptr_enc == 0x3b) {
-            let field_fo = file_off + pos;
-            let field_va = sec.address() + pos as u64;
-            let base_va = if eh_frame_ptr_enc == 0x1b { field_va } else { sec.address() };
-            patches.push(EhPatch { fo: field_fo, field_va: base_va });
-        }
-        
-
-        pos += skip_sz;
-        
-        let fde_count_sz = match eh_pe_fixed_size(fde_count_enc, 8) {
-            Some(sz) => sz,
-            None => continue,
-        };
-        
-        if fde_count_sz == 4 {
-            if pos + 4 > data.len() { continue; }
-            let fde_count = LittleEndian::read_u32(&data[pos..pos+4]) as usize;
-            pos += 4;
-            
-            let table_bytes = fde_count * 8;
-            if pos + table_bytes <= data.len() {
-                for i in 0..(fde_count * 2) {
-                    let field_fo = file_off + pos + (i * 4);
-                    let field_va = sec.address() + (pos as u64) + (i as u64 * 4);
-                    let base_va = if table_enc == 0x1b { field_va } else { sec.address() };
-                    patches.push(EhPatch { fo: field_fo, field_va: base_va });
-                }
-            }
-        }
-    }
-
-    for p in patches {
-        if is_compress {
-            let cur_rel = LittleEndian::read_i32(&out[p.fo..p.fo + 4]);
-            let abs_va = p.field_va.wrapping_add(cur_rel as i64 as u64);
-            let norm32 = abs_va.wrapping_sub(image_base) as u32;
-            
-            if use_be {
-                out[p.fo..p.fo + 4].copy_from_slice(&norm32.to_be_bytes());
-            } else {
-                out[p.fo..p.fo + 4].copy_from_slice(&norm32.to_le_bytes());
-            }
-        } else {
-            let norm32 = if use_be {
-                u32::from_be_bytes(out[p.fo..p.fo + 4].try_into().unwrap())
-            } else {
-                LittleEndian::read_u32(&out[p.fo..p.fo + 4])
-            };
-            
-            let orig_rel = (norm32 as u64).wrapping_add(image_base).wrapping_sub(p.field_va) as u32;
-            LittleEndian::write_u32(&mut out[p.fo..p.fo + 4], orig_rel);
-        }
-    }
-
-    out
-}
-
-
-#[derive(Debug, Clone, Copy)]
-struct CieInfo {
-    fde_ptr_enc: u8,
-    lsda_ptr_enc: Option<u8>,
-    has_z: bool,
-}
-
-fn read_uleb128(buf: &[u8], pos: &mut usize, end: usize) -> Option<u64> {
-    let mut result: u64 = 0;
-    let mut shift: u32 = 0;
-    while *pos < end {
-        let b = buf[*pos];
-        *pos += 1;
-        result |= ((b & 0x7F) as u64) << shift;
-        if (b & 0x80) == 0 { return Some(result); }
-        shift += 7;
-        if shift >= 64 { return None; }
-    }
-    None
-}
-
-fn read_sleb128(buf: &[u8], pos: &mut usize, end: usize) -> Option<i64> {
-    let mut result: i64 = 0;
-    let mut shift: u32 = 0;
-    let mut byte: u8;
-    loop {
-        if *pos >= end { return None; }
-        byte = buf[*pos];
-        *pos += 1;
-        result |= ((byte & 0x7F) as i64) << shift;
-        shift += 7;
-        if (byte & 0x80) == 0 { break; }
-        if shift >= 64 { return None; }
-    }
-    if shift < 64 && (byte & 0x40) != 0 {
-        result |= (!0i64) << shift;
-    }
-    Some(result)
-}
-
-fn read_c_string<'a>(buf: &'a [u8], pos: &mut usize, end: usize) -> Option<&'a [u8]> {
-    if *pos > end { return None; }
-    let start = *pos;
-    let mut i = start;
-    while i < end {
-        if buf[i] == 0 {
-            *pos = i + 1;
-            return Some(&buf[start..i]);
-        }
-        i += 1;
-    }
-    None
-}
-
-fn patch_eh_pointer(
-    out: &mut [u8],
-    file_fo: usize,
-    field_va: u64,
-    enc: u8,
-    image_base: u64,
-    is_compress: bool,
-    use_be: bool,
-) {
-    let sz = match eh_pe_fixed_size(enc, 8) {
-        Some(s) => s,
-        None => return,
-    };
-    if sz == 0 { return; }
-    if file_fo + sz > out.len() { return; }
-
-    let app = enc & 0x70;
-    if app != 0x00 && app != 0x10 { return; }
-
-    match sz {
-        2 => {
-            let base = image_base as u16;
-            let field = field_va as u16;
-            if is_compress {
-                let v = LittleEndian::read_u16(&out[file_fo..file_fo + 2]);
-                let abs = if app == 0x10 { field.wrapping_add(v) } else { v };
-                let norm = abs.wrapping_sub(base);
-                let bytes = if use_be { norm.to_be_bytes() } else { norm.to_le_bytes() };
-                out[file_fo..file_fo + 2].copy_from_slice(&bytes);
-            } else {
-                let norm = if use_be { u16::from_be_bytes(out[file_fo..file_fo + 2].try_into().unwrap()) } 
-                else { LittleEndian::read_u16(&out[file_fo..file_fo + 2]) };
-                let abs = norm.wrapping_add(base);
-                let v = if app == 0x10 { abs.wrapping_sub(field) } else { abs };
-                LittleEndian::write_u16(&mut out[file_fo..file_fo + 2], v);
-            }
-        }
-        4 => {
-            let base = image_base as u32;
-            let field = field_va as u32;
-            if is_compress {
-                let v = LittleEndian::read_i32(&out[file_fo..file_fo + 4]);
-                let abs = if app == 0x10 { field.wrapping_add(v as u32) } else { v as u32 };
-                let norm = abs.wrapping_sub(base);
-                let bytes = if use_be { norm.to_be_bytes() } else { norm.to_le_bytes() };
-                out[file_fo..file_fo + 4].copy_from_slice(&bytes);
-            } else {
-                let norm = if use_be { u32::from_be_bytes(out[file_fo..file_fo + 4].try_into().unwrap()) } 
-                else { LittleEndian::read_u32(&out[file_fo..file_fo + 4]) };
-                let abs = norm.wrapping_add(base);
-                let v = if app == 0x10 { abs.wrapping_sub(field) } else { abs };
-                LittleEndian::write_u32(&mut out[file_fo..file_fo + 4], v);
-            }
-        }
-        8 => {
-            let base = image_base;
-            let field = field_va;
-            if is_compress {
-                let v = LittleEndian::read_i64(&out[file_fo..file_fo + 8]);
-                let abs = if app == 0x10 { field.wrapping_add(v as u64) } else { v as u64 };
-                let norm = abs.wrapping_sub(base);
-                let bytes = if use_be { norm.to_be_bytes() } else { norm.to_le_bytes() };
-                out[file_fo..file_fo + 8].copy_from_slice(&bytes);
-            } else {
-                let norm = if use_be { u64::from_be_bytes(out[file_fo..file_fo + 8].try_into().unwrap()) } 
-                else { LittleEndian::read_u64(&out[file_fo..file_fo + 8]) };
-                let abs = norm.wrapping_add(base);
-                let v = if app == 0x10 { abs.wrapping_sub(field) } else { abs };
-                LittleEndian::write_u64(&mut out[file_fo..file_fo + 8], v);
-            }
-        }
-        _ => {}
-    }
-}
-
-fn process_eh_frame(file_data: &[u8], is_compress: bool, use_be: bool) -> Vec<u8> {
-    let mut out = file_data.to_vec();
-    let obj = match object::File::parse(file_data) {
-        Ok(o) => o,
-        Err(_) => return out,
-    };
-
-    if obj.architecture() != Architecture::X86_64 || !obj.is_little_endian() || !obj.is_64() {
-        return out;
-    }
-
-    let mut image_base = u64::MAX;
-    for seg in obj.segments() {
-        if seg.address() < image_base { image_base = seg.address(); }
-    }
-    if image_base == u64::MAX { image_base = 0; }
-
-    for sec in obj.sections() {
-        if sec.name().unwrap_or("") != ".eh_frame" { continue; }
-
-        let (sec_fo_u64, sec_sz_u64) = match sec.file_range() {
-            Some(r) => r,
-            None => continue,
-        };
-        let sec_fo = sec_fo_u64 as usize;
-        let sec_sz = sec_sz_u64 as usize;
-        if sec_fo + sec_sz > out.len() { continue; }
-
-        let sec_va = sec.address();
-        let data = match sec.data() {
-            Ok(d) => d,
-            Err(_) => continue,
-        };
-        if data.len() != sec_sz { continue; }
-
-        let mut cie_map: HashMap<u32, CieInfo> = HashMap::new();
-        let mut pos = 0usize;
-
-        while pos + 4 <= data.len() {
-            let record_start = pos;
-            let len32 = LittleEndian::read_u32(&data[pos..pos + 4]);
-            if len32 == 0 { break; }
-
-            let (rec_len, header_len) = if len32 == 0xFFFF_FFFF {
-                if pos + 12 > data.len() { break; }
-                let len64 = LittleEndian::read_u64(&data[pos + 4..pos + 12]) as usize;
-                (len64, 12usize)
-            } else {
-                (len32 as usize, 4usize)
-            };
-
-            let record_end = record_start + header_len + rec_len;
-            if record_end > data.len() { break; }
-
-            let id_pos = record_start + header_len;
-            if id_pos + 4 > record_end {
-                pos = record_end;
-                continue;
-            }
-
-            let id = LittleEndian::read_u32(&data[id_pos..id_pos + 4]);
-            let mut p = id_pos + 4;
-
-            if id == 0 {
-                if p >= record_end { pos = record_end; continue; }
-                let version = data[p];
-                p += 1;
-
-                let aug = match read_c_string(data, &mut p, record_end) {
-                    Some(s) => s,
-                    None => { pos = record_end; continue; }
-                };
-
-                if read_uleb128(data, &mut p, record_end).is_none() { pos = record_end; continue; } 
-                if read_sleb128(data, &mut p, record_end).is_none() { pos = record_end; continue; } 
-
-                if version == 1 {
-                    if p >= record_end { pos = record_end; continue; }
-                    p += 1; 
-                } else {
-                    if read_uleb128(data, &mut p, record_end).is_none() { pos = record_end; continue; } 
-                }
-
-                let mut cie = CieInfo { fde_ptr_enc: 0x00, lsda_ptr_enc: None, has_z: false };
-
-                if !aug.is_empty() && aug[0] == b'z' {
-                    cie.has_z = true;
-                    let aug_len = match read_uleb128(data, &mut p, record_end) {
-                        Some(v) => v as usize,
-                        None => { pos = record_end; continue; }
-                    };
-                    if p + aug_len > record_end { pos = record_end; continue; }
-
-                    let aug_end = p + aug_len;
-                    let mut q = p;
-
-                    for &ch in &aug[1..] {
-                        match ch {
-                            b'L' => {
-                                if q >= aug_end { break; }
-                                cie.lsda_ptr_enc = Some(data[q]);
-                                q += 1;
-                            }
-                            b'R' => {
-                                if q >= aug_end { break; }
-                                cie.fde_ptr_enc = data[q];
-                                q += 1;
-                            }
-                            b'P' => {
-                                if q >= aug_end { break; }
-                                let p_enc = data[q];
-                                q += 1;
-
-                                if let Some(sz) = eh_pe_fixed_size(p_enc, 8) {
-                                    if q + sz > aug_end { break; }
-                                    let ptr_off = q;
-                                    patch_eh_pointer(
-                                        &mut out,
-                                        sec_fo + ptr_off,
-                                        sec_va + ptr_off as u64,
-                                        p_enc,
-                                        image_base,
-                                        is_compress,
-                                        use_be,
-                                    );
-                                    q += sz;
-                                } else { break; }
-                            }
-                            b'S' => {}
-                            _ => break, 
-                        }
-                    }
-                    
-                }
-                cie_map.insert(record_start as u32, cie);
-            } else {
-                let cie_start = (id_pos as u32).wrapping_sub(id);
-                let cie = match cie_map.get(&cie_start) {
-                    Some(c) => *c,
-                    None => { pos = record_end; continue; }
-                };
-
-                let ptr_sz = match eh_pe_fixed_size(cie.fde_ptr_enc, 8) {
-                    Some(sz) if sz > 0 => sz,
-                    _ => { pos = record_end; continue; }
-                };
-
-                if p + ptr_sz * 2 > record_end { pos = record_end; continue; }
-
-                let init_off = p;
-                patch_eh_pointer(
-                    &mut out,
-                    sec_fo + init_off,
-                    sec_va + init_off as u64,
-                    cie.fde_ptr_enc,
-                    image_base,
-                    is_compress,
-                    use_be,
-                );
-
-                p += ptr_sz; 
-                p += ptr_sz; 
-
-                if cie.has_z {
-                    let aug_len = match read_uleb128(data, &mut p, record_end) {
-                        Some(v) => v as usize,
-                        None => { pos = record_end; continue; }
-                    };
-                    if p + aug_len > record_end { pos = record_end; continue; }
-
-                    let aug_start = p;
-
-                    if let Some(lsda_enc) = cie.lsda_ptr_enc {
-                        if let Some(lsda_sz) = eh_pe_fixed_size(lsda_enc, 8) {
-                            if lsda_sz > 0 && aug_start + lsda_sz <= aug_start + aug_len {
-                                patch_eh_pointer(
-                                    &mut out,
-                                    sec_fo + aug_start,
-                                    sec_va + aug_start as u64,
-                                    lsda_enc,
-                                    image_base,
-                                    is_compress,
-                                    use_be,
-                                );
-                            }
-                        }
-                    }
-                    
-                }
-            }
-            pos = record_end;
-        }
-    }
-    out
-}
-
-// ---------------- USASE Patching ----------------
-
-#[derive(Debug, Clone, Copy)]
-struct Patch {
-    fo: usize,
-    next_ip: u32,
-}
-
-fn process_binary(file_data: &[u8], is_compress: bool, use_be: bool) -> Vec<u8> {
-    let mut skel = file_data.to_vec();
-    let obj = match object::File::parse(file_data) { Ok(o) => o, Err(_) => return skel };
-    let mut image_base = u64::MAX;
-    for sec in obj.segments() {
-        if sec.address() < image_base { image_base = sec.address(); }
-    }
-    if image_base == u64::MAX { image_base = 0; }
-    if obj.architecture() != Architecture::X86_64 || !obj.is_little_endian() || !obj.is_64() { return skel; }
-
-    let mut patches: Vec<Patch> = Vec::new();
-
-    for sec in obj.sections() {
-        if sec.kind() != SectionKind::Text { continue; }
-        let (file_off, file_size) = match sec.file_range() { Some(r) => r, None => continue };
-        let file_off = file_off as usize;
-        let file_size = file_size as usize;
-        let data = match sec.data() { Ok(d) => d, Err(_) => continue };
-
-        if data.len() != file_size { continue; }
-        if file_off + data.len() > skel.len() { continue; }
-
-        let va = sec.address();
-        let mut decoder = Decoder::with_ip(64, data, va, DecoderOptions::NONE);
-
-        while decoder.can_decode() {
-            let inst = decoder.decode();
-            let inst_ip = inst.ip();
-            let inst_len = inst.len();
-            let next_ip = inst_ip.wrapping_add(inst_len as u64) as u32;
-
-            let off_in_sec = (inst_ip - va) as usize;
-            if off_in_sec + inst_len > data.len() { break; }
-            let inst_fo = file_off + off_in_sec;
-
-            let co = decoder.get_constant_offsets(&inst);
-
-            if inst.is_ip_rel_memory_operand() && co.has_displacement() && co.displacement_size() == 4 {
-                let fo = inst_fo + co.displacement_offset();
-                if fo + 4 <= skel.len() { patches.push(Patch { fo, next_ip }); }
-            }
-
-            if (inst.is_call_near() || inst.is_jmp_near() || inst.is_jcc_short_or_near()) && co.has_immediate() && co.immediate_size() == 4 {
-                let fo = inst_fo + co.immediate_offset();
-                if fo + 4 <= skel.len() { patches.push(Patch { fo, next_ip }); }
-            }
-        }
-    }
-
-    for p in &patches {
-        if is_compress {
-            let cur = LittleEndian::read_u32(&skel[p.fo..p.fo + 4]);
-            let dest = cur.wrapping_add(p.next_ip);
-            let norm = dest.wrapping_sub(image_base as u32);
-            if use_be { skel[p.fo..p.fo + 4].copy_from_slice(&norm.to_be_bytes()); } 
-            else { skel[p.fo..p.fo + 4].copy_from_slice(&norm.to_le_bytes()); }
-        } else {
-            let norm = if use_be { u32::from_be_bytes(skel[p.fo..p.fo + 4].try_into().unwrap()) } 
-            else { LittleEndian::read_u32(&skel[p.fo..p.fo + 4]) };
-            let dest = norm.wrapping_add(image_base as u32);
-            let orig = dest.wrapping_sub(p.next_ip);
-            LittleEndian::write_u32(&mut skel[p.fo..p.fo + 4], orig);
-        }
-    }
-
-    skel
-}
-
-// ---------------- Routing ----------------
-
-fn split_streams(file_data: &[u8], jump_tables: &[JumpTable]) -> (Vec<u8>, Vec<Vec<u8>>) {
-    let mut labels = vec![CAT_OTHER; file_data.len()];
-    let ptr_prefixes = [".got", ".got.plt", ".data.rel.ro", ".init_array", ".fini_array", ".plt.got"];
-
-    if let Ok(obj) = object::File::parse(file_data) {
-        for sec in obj.sections() {
-            let (fo, size) = match sec.file_range() { Some(r) => r, None => continue };
-            let fo = fo as usize;
-            let size = size as usize;
-            if fo + size > file_data.len() { continue; }
-
-            let mut cat = CAT_OTHER;
-            let name = sec.name().unwrap_or("");
-
-            if sec.kind() == SectionKind::Text {
-                cat = CAT_CODE;
-            } else if name == ".strtab" || name == ".dynstr" || name.contains("str") {
-                cat = CAT_STR;
-            } else if name.contains("eh_frame") || name.contains("gcc_except") {
-                cat = CAT_EH;
-            } else if name.starts_with(".relr") {
-                cat = CAT_RELR8;
-            } else if name.starts_with(".rela") {
-                cat = CAT_RELA24; 
-            } else if name == ".symtab" || name == ".dynsym" {
-                cat = CAT_SYM24;
-            } else if name.starts_with(".rel") {
-                cat = CAT_REL16; 
-            } else if name == ".dynamic" {
-                cat = CAT_DYNAMIC16; 
-            } else if name.contains("cst16") {
-                cat = CAT_S16;
-            } else if name == ".gnu.hash" {
-                cat = CAT_GNUHASH;
-            } else if name == ".gnu.version" {
-                cat = CAT_S2;
-            } else if ptr_prefixes.iter().any(|p| name.starts_with(p)) || name.contains("array") || name.contains("cst8") {
-                cat = CAT_S8; 
-            } else if name.contains("hash") || name.contains("cst4") {
-                cat = CAT_S4; 
-            }
-
-            for i in fo..fo + size { labels[i] = cat; }
-        }
-    }
-
-    for t in jump_tables {
-        for i in t.fo .. t.fo + (t.count * 4) {
-            if i < labels.len() { labels[i] = CAT_JT4; }
-        }
-    }
-    let mut runs = Vec::new();
-    if !labels.is_empty() {
-        let mut cur_cat = labels[0];
-        let mut count = 1u64;
-        for &cat in &labels[1..] {
-            if cat == cur_cat { count += 1; } 
-            else {
-                write_varint(&mut runs, (count << 4) | (cur_cat as u64));
-                cur_cat = cat;
-                count = 1;
-            }
-        }
-        write_varint(&mut runs, (count << 4) | (cur_cat as u64));
-    }
-
-    let mut streams = vec![Vec::new(); CAT_COUNT];
-    for (i, &cat) in labels.iter().enumerate() { streams[cat as usize].push(file_data[i]); }
-    (runs, streams)
-}
-
-fn compress_with_mode(file_data: &[u8], use_be: bool) -> Vec<u8> {
-    let skel = process_binary(file_data, true, use_be);
-    let skel = process_eh_frame_hdr(&skel, true, use_be);
-    let skel = process_eh_frame(&skel, true, use_be);
-    let (skel, jt_meta, jump_tables) = process_jump_tables(&skel, true, use_be, None).unwrap();
-    let skel = process_elf_tables(&skel, true);
-    
-    let (runs, mut streams) = split_streams(&skel, &jump_tables);
-
-    let preset = 9 | PRESET_EXTREME;
-    let strides = [
-        (CAT_S2, 2usize), (CAT_S4, 4usize), (CAT_S8, 8usize), (CAT_RELR8, 8usize),
-        (CAT_S16, 16usize), (CAT_REL16, 16usize), (CAT_DYNAMIC16, 16usize), 
-        (CAT_S24, 24usize), (CAT_RELA24, 24usize), (CAT_SYM24, 24usize),
-        (CAT_JT4, 4usize)
-    ];
-    for (cat, stride) in strides {
-        let s = &mut streams[cat as usize];
-        bswap_cat(s, cat as usize);
-        *s = shuffle_bytes(s, stride);
-    }
-
-
-    let fused_cap: usize = NUM_FUSED_ORDER.iter().map(|&c| streams[c].len()).sum();
-    let mut num_fused = Vec::with_capacity(fused_cap);
-    for &c in &NUM_FUSED_ORDER {
-        num_fused.append(&mut streams[c]);
-    }
-    streams[FUSED_NUM_BLOCK_CAT] = num_fused;
-    
-    let txt_cap: usize = TXT_FUSED_ORDER.iter().map(|&c| streams[c].len()).sum();
-    let mut txt_fused = Vec::with_capacity(txt_cap);
-    for &c in &TXT_FUSED_ORDER {
-        txt_fused.append(&mut streams[c]);
-    }
-    streams[FUSED_TXT_BLOCK_CAT] = txt_fused;
-
-    let blocks: Vec<Block> = streams.into_par_iter().enumerate().map(|(cat, s)| {
-        if s.is_empty() { return Block { method: 0, payload: Vec::new() }; }
-        let pb = choose_pb(cat);
-        let dict = choose_dict_size(s.len());
-        
-        let compressed_best = if cat != CAT_CODE as usize && cat != CAT_EH as usize && cat != CAT_OTHER as usize {
-            let mut opts_lc3 = LzmaOptions::new_preset(preset).unwrap();
-            opts_lc3.position_bits(pb).dict_size(dict).literal_context_bits(3);
-            let mut f3 = Filters::new(); f3.lzma2(&opts_lc3);
-            let mut enc3 = xz2::write::XzEncoder::new_stream(Vec::new(), Stream::new_stream_encoder(&f3, XZ_CHECK).unwrap());
-            enc3.write_all(&s).unwrap();
-            let mut c_best = enc3.finish().unwrap();
-
-            let mut opts_lc0 = LzmaOptions::new_preset(preset).unwrap();
-            opts_lc0.position_bits(pb).dict_size(dict).literal_context_bits(0);
-            let mut f0 = Filters::new(); f0.lzma2(&opts_lc0);
-            let mut enc0 = xz2::write::XzEncoder::new_stream(Vec::new(), Stream::new_stream_encoder(&f0, XZ_CHECK).unwrap());
-            enc0.write_all(&s).unwrap();
-            let c0 = enc0.finish().unwrap();
-            if c0.len() < c_best.len() { c_best = c0; }
-
-            c_best
-        } else {
-            compress_xz_tuned(&s, preset, pb, dict)
-        };
-
-        if compressed_best.len() < s.len() {
-            Block { method: 1, payload: compressed_best }
-        } else {
-            Block { method: 0, payload: s }
-        }
-    }).collect();
-
-    let mut out = Vec::new();
-    out.extend_from_slice(MAGIC);
-    out.push(FORMAT_VERSION);
-
-    let mut orig_len_buf = [0u8; 8];
-    LittleEndian::write_u64(&mut orig_len_buf, file_data.len() as u64);
-    out.extend_from_slice(&orig_len_buf);
-
-    out.push(if use_be { 1 } else { 0 });
-
-    write_varint(&mut out, runs.len() as u64);
-    out.extend_from_slice(&runs);
-
-    for b in blocks {
-        write_block(&mut out, b.method, &b.payload);
-    }
-    
-    write_varint(&mut out, jt_meta.len() as u64);
-    out.extend_from_slice(&jt_meta);
-
-    out
-}
-
-fn compress(file_data: &[u8]) -> Vec<u8> {
-    let (c_le, c_be) = rayon::join(|| compress_with_mode(file_data, false), || compress_with_mode(file_data, true));
-    if c_be.len() < c_le.len() { c_be } else { c_le }
-}
-
-fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
-    if data.len() < 14 { return Err("input too short".into()); }
-    if &data[0..4] != MAGIC { return Err("bad magic".into()); }
-    let version = data[4];
-    if version != FORMAT_VERSION { return Err("unsupported format version".into()); }
-    let orig_len = LittleEndian::read_u64(&data[5..13]) as usize;
-    let mut pos = 13usize;
-    let use_be = data[pos] == 1;
-    pos += 1;
-
-    let runs_len = read_varint(data, &mut pos)? as usize;
-    if pos + runs_len > data.len() { return Err("runs block out of range".into()); }
-    let runs_data = &data[pos..pos + runs_len];
-    pos += runs_len;
-
-    let mut blocks: Vec<(u8, &[u8])> = Vec::with_capacity(CAT_COUNT);
-    for _ in 0..CAT_COUNT {
-        let (method, payload) = read_block(data, &mut pos)?;
-        blocks.push((method, payload));
-    }
-    
-    let jt_meta_len = read_varint(data, &mut pos)? as usize;
-    if pos + jt_meta_len > data.len() { return Err("jt block out of range".into()); }
-    let jt_meta = &data[pos..pos + jt_meta_len];
-
-
-    // Compute cat_lens early to unfuse
-    let mut runs_vec: Vec<(usize, usize)> = Vec::new();
-    let mut cat_lens = [0usize; CAT_COUNT];
-    {
-        let mut rp = 0usize;
-        while rp < runs_data.len() {
-            let val = read_varint(runs_data, &mut rp)?;
-            let cat = (val & 15) as usize;
-            let count = (val >> 4) as usize;
-            if cat >= CAT_COUNT { return Err("bad category".into()); }
-            runs_vec.push((cat, count));
-            cat_lens[cat] = cat_lens[cat].saturating_add(count);
-        }
-    }
-
-    let mut decompressed_streams: Vec<Vec<u8>> = blocks.par_iter()
-        .map(|(method, payload)| {
-            if *method == 0 { Ok(payload.to_vec()) } else { decompress_xz(payload) }
-        }).collect::<Result<Vec<_>, _>>()?;
-
-    {
-        let mut fused = std::mem::take(&mut decompressed_streams[FUSED_NUM_BLOCK_CAT]);
-        let mut expected = 0usize;
-        for &c in &NUM_FUSED_ORDER { expected = expected.saturating_add(cat_lens[c]); }
-        if fused.len() != expected {
-            return Err(format!("num fused stream mismatch: got {} expected {}", fused.len(), expected));
-        }
-
-        let mut total = fused.len();
-        for &c in NUM_FUSED_ORDER.iter().rev() {
-            let len = cat_lens[c];
-            if len > total { return Err("num fused split underflow".into()); }
-            let start = total - len;
-            let part = fused.split_off(start);
-            decompressed_streams[c] = part;
-            total = start;
-        }
-        if !fused.is_empty() { return Err("num fused split leftover bytes".into()); }
-    }
-    
-    {
-        let mut fused = std::mem::take(&mut decompressed_streams[FUSED_TXT_BLOCK_CAT]);
-        let mut expected = 0usize;
-        for &c in &TXT_FUSED_ORDER { expected = expected.saturating_add(cat_lens[c]); }
-        if fused.len() != expected {
-            return Err(format!("txt fused stream mismatch: got {} expected {}", fused.len(), expected));
-        }
-
-        let mut total = fused.len();
-        for &c in TXT_FUSED_ORDER.iter().rev() {
-            let len = cat_lens[c];
-            if len > total { return Err("txt fused split underflow".into()); }
-            let start = total - len;
-            let part = fused.split_off(start);
-            decompressed_streams[c] = part;
-            total = start;
-        }
-        if !fused.is_empty() { return Err("txt fused split leftover bytes".into()); }
-    }
-
-
-    let strides = [
-        (CAT_S2, 2usize), (CAT_S4, 4usize), (CAT_S8, 8usize), (CAT_RELR8, 8usize),
-        (CAT_S16, 16usize), (CAT_REL16, 16usize), (CAT_DYNAMIC16, 16usize), 
-        (CAT_S24, 24usize), (CAT_RELA24, 24usize), (CAT_SYM24, 24usize),
-        (CAT_JT4, 4usize)
-    ];
-    for (cat, stride) in strides {
-        let s = &mut decompressed_streams[cat as usize];
-        *s = unshuffle_bytes(s, stride);
-        bswap_cat(s, cat as usize);
-    }
-
-    let mut skel = vec![0u8; orig_len];
-    let mut cursors = vec![0usize; CAT_COUNT];
-    let mut skel_pos = 0usize;
-    for &(cat, count) in &runs_vec {
-        if skel_pos + count > skel.len() { return Err("runs exceed output length".into()); }
-        let c = cursors[cat];
-        if c + count > decompressed_streams[cat].len() { return Err("stream underflow while reconstructing".into()); }
-
-        skel[skel_pos..skel_pos + count].copy_from_slice(&decompressed_streams[cat][c..c + count]);
-        cursors[cat] += count;
-        skel_pos += count;
-    }
-
-    for cat in 0..CAT_COUNT {
-        if cursors[cat] != decompressed_streams[cat].len() {
-            return Err(format!("stream {} has extra bytes: used {} / {}", cat, cursors[cat], decompressed_streams[cat].len()));
-        }
-    }
-
-    let skel = process_elf_tables(&skel, false);
-    let (skel, _, _) = process_jump_tables(&skel, false, use_be, Some(jt_meta))?;
-    let skel = process_eh_frame(&skel, false, use_be);
-    let skel = process_eh_frame_hdr(&skel, false, use_be);
-    Ok(process_binary(&skel, false, use_be))
-}
-
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 3 { std::process::exit(2); }
-    let cmd = &args[1];
-    let path = &args[2];
-
-    match cmd.as_str() {
-        "compare" => {
-            let data = fs::read(path).unwrap();
-            let start = Instant::now();
-            let compressed = compress(&data);
-            let c_time = start.elapsed();
-            let start = Instant::now();
-            let decompressed = decompress(&compressed).unwrap();
-            let d_time = start.elapsed();
-            assert_eq!(data, decompressed, "Mismatch!");
-
-            println!("====== FESH USASE vG (EH_FRAME_HDR + Jump Tables + LC0 MoE) ======");
-            println!("Target File: {}", path);
-            println!("Input:       {} bytes", data.len());
-            let ratio = (compressed.len() as f64 / data.len() as f64) * 100.0;
-            println!("FESH (Rust): {} bytes ({:.2}%)", compressed.len(), ratio);
-            println!("Comp Time:   {:?}", c_time);
-            println!("Decomp Time: {:?}", d_time);
-        }
-        "compress" => {
-            let data = fs::read(path).unwrap();
-            fs::write(&args[3], compress(&data)).unwrap();
-        }
-        "decompress" => {
-            let data = fs::read(path).unwrap();
-            fs::write(&args[3], decompress(&data).unwrap()).unwrap();
-        }
-        _ => { std::process::exit(2); }
-    }
+    fesh_comp::cli::main()
 }